@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::fs::File;
 use std::io::Read;
 use std::sync::{Arc, Mutex};
@@ -13,6 +14,10 @@ const CRAMFS_MAGIC_BE: u32 = 0x453dcd28;   // CramFS big endian
 const JFFS2_MAGIC_BITMASK: u16 = 0x1985;   // JFFS2 uses 16-bit magic
 const JFFS2_MAGIC_BITMASK_BE: u16 = 0x8519; // JFFS2 big endian
 
+// Entropy/chi-squared scoring, for candidates that don't hit an exact magic
+const SCORE_WINDOW: usize = 4 * 1024; // 4 KB scoring window
+const TOP_N_CANDIDATES: usize = 20;
+
 #[derive(Debug)]
 struct FilesystemMatch {
     offset: usize,
@@ -20,6 +25,92 @@ struct FilesystemMatch {
     endian: String,
 }
 
+#[derive(Debug, Clone)]
+struct ScoredCandidate {
+    key: Vec<u8>,
+    min_entropy: f64,
+    chi_squared: f64,
+}
+
+// Shannon entropy over the 256-bin byte histogram of `window`, in bits/byte.
+// Structured data (text, code, filesystem metadata) sits well below 8.0;
+// still-encrypted/random data stays close to it.
+fn shannon_entropy(window: &[u8]) -> f64 {
+    let mut counts = [0u32; 256];
+    for &b in window {
+        counts[b as usize] += 1;
+    }
+
+    let len = window.len() as f64;
+    counts
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+// Chi-squared statistic against a uniform byte distribution. High values
+// indicate non-random structure in the window.
+fn chi_squared(window: &[u8]) -> f64 {
+    let mut counts = [0u32; 256];
+    for &b in window {
+        counts[b as usize] += 1;
+    }
+
+    let expected = window.len() as f64 / 256.0;
+    counts
+        .iter()
+        .map(|&c| {
+            let diff = c as f64 - expected;
+            diff * diff / expected
+        })
+        .sum()
+}
+
+// Number of evenly spaced SCORE_WINDOW samples taken per key in the hot
+// brute-force loop, in place of a full sliding scan.
+const SCORE_SAMPLE_COUNT: usize = 4;
+
+// Cheap per-key entropy/chi-squared score: samples a handful of
+// evenly-spaced SCORE_WINDOW windows instead of sliding one across the
+// whole buffer. Scoring every non-matching key with a full sliding scan
+// turns the brute force effectively non-terminating; this keeps scoring
+// cost flat per key regardless of buffer size.
+fn score_buffer_sampled(data: &[u8]) -> (f64, f64) {
+    if data.len() <= SCORE_WINDOW {
+        return (shannon_entropy(data), chi_squared(data));
+    }
+
+    let max_start = data.len() - SCORE_WINDOW;
+    let mut min_entropy = f64::MAX;
+    let mut chi2_at_min = 0.0;
+
+    for i in 0..SCORE_SAMPLE_COUNT {
+        let start = max_start * i / (SCORE_SAMPLE_COUNT - 1).max(1);
+        let window = &data[start..start + SCORE_WINDOW];
+        let h = shannon_entropy(window);
+        if h < min_entropy {
+            min_entropy = h;
+            chi2_at_min = chi_squared(window);
+        }
+    }
+
+    (min_entropy, chi2_at_min)
+}
+
+// Keeps only the TOP_N_CANDIDATES lowest-entropy candidates, compacting
+// periodically so a long-running scan doesn't accumulate one entry per key.
+fn record_candidate(candidates: &mut Vec<ScoredCandidate>, candidate: ScoredCandidate) {
+    candidates.push(candidate);
+    if candidates.len() > TOP_N_CANDIDATES * 4 {
+        candidates.sort_by(|a, b| a.min_entropy.partial_cmp(&b.min_entropy).unwrap());
+        candidates.truncate(TOP_N_CANDIDATES);
+    }
+}
+
 // RC4 implementation
 struct RC4 {
     s: [u8; 256],
@@ -64,63 +155,337 @@ fn rc4_decrypt(data: &[u8], key: &[u8]) -> Vec<u8> {
     rc4.decrypt(data)
 }
 
+// --- BLAKE3 (minimal, single-threaded) ---------------------------------
+//
+// Used only to fingerprint the slab starting at a validated superblock hit,
+// so that hits of the same fs type whose slabs are byte-identical collapse
+// to one result within a single find_filesystem_magic call (hits from
+// different keys are not compared).
+// This is not exposed as a general-purpose hashing utility; it implements
+// just enough of the BLAKE3 tree to hash arbitrary byte slices.
+
+const BLAKE3_IV: [u32; 8] = [
+    0x6A09E667, 0xBB67AE85, 0x3C6EF372, 0xA54FF53A,
+    0x510E527F, 0x9B05688C, 0x1F83D9AB, 0x5BE0CD19,
+];
+
+const BLAKE3_MSG_PERMUTATION: [usize; 16] = [2, 6, 3, 10, 7, 0, 4, 13, 1, 11, 12, 5, 9, 14, 15, 8];
+
+const CHUNK_START: u32 = 1 << 0;
+const CHUNK_END: u32 = 1 << 1;
+const ROOT: u32 = 1 << 3;
+const PARENT: u32 = 1 << 2;
+
+const BLAKE3_CHUNK_LEN: usize = 1024;
+const BLAKE3_BLOCK_LEN: usize = 64;
+
+fn blake3_g(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize, mx: u32, my: u32) {
+    state[a] = state[a].wrapping_add(state[b]).wrapping_add(mx);
+    state[d] = (state[d] ^ state[a]).rotate_right(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_right(12);
+    state[a] = state[a].wrapping_add(state[b]).wrapping_add(my);
+    state[d] = (state[d] ^ state[a]).rotate_right(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_right(7);
+}
+
+fn blake3_round(state: &mut [u32; 16], m: &[u32; 16]) {
+    blake3_g(state, 0, 4, 8, 12, m[0], m[1]);
+    blake3_g(state, 1, 5, 9, 13, m[2], m[3]);
+    blake3_g(state, 2, 6, 10, 14, m[4], m[5]);
+    blake3_g(state, 3, 7, 11, 15, m[6], m[7]);
+    blake3_g(state, 0, 5, 10, 15, m[8], m[9]);
+    blake3_g(state, 1, 6, 11, 12, m[10], m[11]);
+    blake3_g(state, 2, 7, 8, 13, m[12], m[13]);
+    blake3_g(state, 3, 4, 9, 14, m[14], m[15]);
+}
+
+fn blake3_permute(m: &mut [u32; 16]) {
+    let mut permuted = [0u32; 16];
+    for i in 0..16 {
+        permuted[i] = m[BLAKE3_MSG_PERMUTATION[i]];
+    }
+    *m = permuted;
+}
+
+#[allow(clippy::too_many_arguments)]
+fn blake3_compress(
+    chaining_value: &[u32; 8],
+    block_words: &[u32; 16],
+    counter: u64,
+    block_len: u32,
+    flags: u32,
+) -> [u32; 16] {
+    let mut state = [
+        chaining_value[0], chaining_value[1], chaining_value[2], chaining_value[3],
+        chaining_value[4], chaining_value[5], chaining_value[6], chaining_value[7],
+        BLAKE3_IV[0], BLAKE3_IV[1], BLAKE3_IV[2], BLAKE3_IV[3],
+        counter as u32, (counter >> 32) as u32, block_len, flags,
+    ];
+    let mut m = *block_words;
+
+    for round in 0..7 {
+        blake3_round(&mut state, &m);
+        if round < 6 {
+            blake3_permute(&mut m);
+        }
+    }
+
+    for i in 0..8 {
+        state[i] ^= state[i + 8];
+        state[i + 8] ^= chaining_value[i];
+    }
+    state
+}
+
+fn blake3_words_from_block(block: &[u8]) -> [u32; 16] {
+    let mut padded = [0u8; BLAKE3_BLOCK_LEN];
+    padded[..block.len()].copy_from_slice(block);
+    let mut words = [0u32; 16];
+    for i in 0..16 {
+        words[i] = u32::from_le_bytes([
+            padded[i * 4], padded[i * 4 + 1], padded[i * 4 + 2], padded[i * 4 + 3],
+        ]);
+    }
+    words
+}
+
+// Chains the (up to 16) 64-byte blocks of a single 1024-byte chunk, returning
+// the chunk's chaining value (or, for the root's sole chunk, its output
+// words when `flags` already carries ROOT).
+fn blake3_chunk_chaining_value(chunk: &[u8], chunk_counter: u64, flags: u32) -> [u32; 8] {
+    let mut cv = BLAKE3_IV;
+    let blocks: Vec<&[u8]> = chunk.chunks(BLAKE3_BLOCK_LEN).collect();
+    let num_blocks = blocks.len().max(1);
+
+    for (i, block) in blocks.iter().enumerate() {
+        let mut block_flags = flags & !ROOT;
+        if i == 0 {
+            block_flags |= CHUNK_START;
+        }
+        if i == num_blocks - 1 {
+            block_flags |= CHUNK_END | (flags & ROOT);
+        }
+        let words = blake3_words_from_block(block);
+        let out = blake3_compress(&cv, &words, chunk_counter, block.len() as u32, block_flags);
+        cv = [out[0], out[1], out[2], out[3], out[4], out[5], out[6], out[7]];
+    }
+
+    if blocks.is_empty() {
+        let words = blake3_words_from_block(&[]);
+        let out = blake3_compress(&cv, &words, chunk_counter, 0, flags | CHUNK_START | CHUNK_END);
+        cv = [out[0], out[1], out[2], out[3], out[4], out[5], out[6], out[7]];
+    }
+
+    cv
+}
+
+fn blake3_parent_cv(left: &[u32; 8], right: &[u32; 8], flags: u32) -> [u32; 8] {
+    let mut block_words = [0u32; 16];
+    block_words[..8].copy_from_slice(left);
+    block_words[8..].copy_from_slice(right);
+    let out = blake3_compress(&BLAKE3_IV, &block_words, 0, BLAKE3_BLOCK_LEN as u32, flags | PARENT);
+    [out[0], out[1], out[2], out[3], out[4], out[5], out[6], out[7]]
+}
+
+fn largest_power_of_two_leq(n: usize) -> usize {
+    1usize << (63 - (n as u64).leading_zeros())
+}
+
+// Recursively reduces `chunks` to a single chaining value, applying `flags`
+// (which carries ROOT) only at the final parent/chunk reduction.
+fn blake3_recurse(chunks: &[&[u8]], counter_start: u64, flags: u32) -> [u32; 8] {
+    if chunks.len() == 1 {
+        return blake3_chunk_chaining_value(chunks[0], counter_start, flags);
+    }
+
+    let split = largest_power_of_two_leq(chunks.len() - 1).max(1);
+    let left_cv = blake3_recurse(&chunks[..split], counter_start, 0);
+    let right_cv = blake3_recurse(&chunks[split..], counter_start + split as u64, 0);
+    blake3_parent_cv(&left_cv, &right_cv, flags)
+}
+
+fn blake3_hash(data: &[u8]) -> [u8; 32] {
+    let chunks: Vec<&[u8]> = if data.is_empty() {
+        vec![&[][..]]
+    } else {
+        data.chunks(BLAKE3_CHUNK_LEN).collect()
+    };
+
+    let root_cv = blake3_recurse(&chunks, 0, ROOT);
+
+    let mut out = [0u8; 32];
+    for (i, word) in root_cv.iter().enumerate() {
+        out[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+    }
+    out
+}
+
+// --- Superblock validation and result dedup ---------------------------
+//
+// A bare 4-byte magic match produces a flood of false positives across the
+// keyspace (random data hits 0x1985 roughly every 64 KB). Once a magic is
+// found, the actual superblock fields are parsed and sanity-checked before
+// the match is reported, and a BLAKE3 hash of the SLAB_SIZE slab starting
+// at each validated superblock drops any later hit in the same buffer whose
+// (fs type, slab hash) pair was already reported. Hits at different offsets
+// only collapse when their slabs are byte-identical.
+
+const SLAB_SIZE: usize = 64 * 1024;
+
+fn is_power_of_two(n: u32) -> bool {
+    n != 0 && (n & (n - 1)) == 0
+}
+
+fn read_u16(data: &[u8], pos: usize, big_endian: bool) -> Option<u16> {
+    let bytes = data.get(pos..pos + 2)?;
+    Some(if big_endian {
+        u16::from_be_bytes([bytes[0], bytes[1]])
+    } else {
+        u16::from_le_bytes([bytes[0], bytes[1]])
+    })
+}
+
+fn read_u32(data: &[u8], pos: usize, big_endian: bool) -> Option<u32> {
+    let bytes = data.get(pos..pos + 4)?;
+    Some(if big_endian {
+        u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    } else {
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    })
+}
+
+// Squashfs 4.x superblock: inode_count at +4, block_size at +12,
+// compression_id at +20 (all relative to the magic).
+fn validate_squashfs_superblock(data: &[u8], offset: usize, big_endian: bool) -> bool {
+    let inode_count = match read_u32(data, offset + 4, big_endian) {
+        Some(v) => v,
+        None => return false,
+    };
+    let block_size = match read_u32(data, offset + 12, big_endian) {
+        Some(v) => v,
+        None => return false,
+    };
+    let compression_id = match read_u16(data, offset + 20, big_endian) {
+        Some(v) => v,
+        None => return false,
+    };
+
+    inode_count > 0
+        && (4096..=1_048_576).contains(&block_size)
+        && is_power_of_two(block_size)
+        && (1..=6).contains(&compression_id)
+}
+
+// CramFS superblock: `size` at +4 must not exceed the buffer we actually have.
+fn validate_cramfs_superblock(data: &[u8], offset: usize, big_endian: bool) -> bool {
+    match read_u32(data, offset + 4, big_endian) {
+        Some(size) => (size as usize) <= data.len(),
+        None => false,
+    }
+}
+
+const CRC32_POLY: u32 = 0xEDB88320;
+
+// mtd-utils/kernel compute JFFS2's hdr_crc as crc32(0, node, 8) — seed 0 and
+// no final bit-invert, unlike the standard reflected CRC-32 (init 0xFFFFFFFF,
+// final invert) that this name would otherwise imply. The standard variant
+// rejects every genuine JFFS2 node, hence the distinct name.
+fn jffs2_crc32(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (CRC32_POLY & mask);
+        }
+    }
+    crc
+}
+
+// JFFS2 unknown-node header: magic(2) nodetype(2) totlen(4) hdr_crc(4).
+// hdr_crc is the JFFS2-variant CRC32 of the first 8 header bytes.
+fn validate_jffs2_node(data: &[u8], offset: usize, big_endian: bool) -> bool {
+    if offset + 12 > data.len() {
+        return false;
+    }
+    let stored_crc = match read_u32(data, offset + 8, big_endian) {
+        Some(v) => v,
+        None => return false,
+    };
+    jffs2_crc32(&data[offset..offset + 8]) == stored_crc
+}
+
+fn validate_superblock(data: &[u8], offset: usize, fs_type: &str, big_endian: bool) -> bool {
+    match fs_type {
+        "Squashfs" => validate_squashfs_superblock(data, offset, big_endian),
+        "CramFS" => validate_cramfs_superblock(data, offset, big_endian),
+        "JFFS2" => validate_jffs2_node(data, offset, big_endian),
+        _ => true,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn record_validated_match(
+    data: &[u8],
+    offset: usize,
+    fs_type: &str,
+    endian: &str,
+    big_endian: bool,
+    seen_slabs: &mut HashSet<(String, [u8; 32])>,
+    matches: &mut Vec<FilesystemMatch>,
+) {
+    if !validate_superblock(data, offset, fs_type, big_endian) {
+        return;
+    }
+
+    let slab_end = (offset + SLAB_SIZE).min(data.len());
+    let hash = blake3_hash(&data[offset..slab_end]);
+
+    if seen_slabs.insert((fs_type.to_string(), hash)) {
+        matches.push(FilesystemMatch {
+            offset,
+            fs_type: fs_type.to_string(),
+            endian: endian.to_string(),
+        });
+    }
+}
+
 fn find_filesystem_magic(data: &[u8]) -> Vec<FilesystemMatch> {
     let mut matches = Vec::new();
-    
+    let mut seen_slabs: HashSet<(String, [u8; 32])> = HashSet::new();
+
     // Need at least 4 bytes for magic number
     if data.len() < 4 {
         return matches;
     }
-    
+
     // Scan through data looking for magic bytes
     for i in 0..=(data.len() - 4) {
         // Check 32-bit magics (Squashfs, CramFS)
         let magic32 = u32::from_le_bytes([data[i], data[i+1], data[i+2], data[i+3]]);
-        
+
         if magic32 == SQUASHFS_MAGIC_LE {
-            matches.push(FilesystemMatch {
-                offset: i,
-                fs_type: "Squashfs".to_string(),
-                endian: "Little Endian".to_string(),
-            });
+            record_validated_match(data, i, "Squashfs", "Little Endian", false, &mut seen_slabs, &mut matches);
         } else if magic32 == SQUASHFS_MAGIC_BE {
-            matches.push(FilesystemMatch {
-                offset: i,
-                fs_type: "Squashfs".to_string(),
-                endian: "Big Endian".to_string(),
-            });
+            record_validated_match(data, i, "Squashfs", "Big Endian", true, &mut seen_slabs, &mut matches);
         } else if magic32 == CRAMFS_MAGIC {
-            matches.push(FilesystemMatch {
-                offset: i,
-                fs_type: "CramFS".to_string(),
-                endian: "Little Endian".to_string(),
-            });
+            record_validated_match(data, i, "CramFS", "Little Endian", false, &mut seen_slabs, &mut matches);
         } else if magic32 == CRAMFS_MAGIC_BE {
-            matches.push(FilesystemMatch {
-                offset: i,
-                fs_type: "CramFS".to_string(),
-                endian: "Big Endian".to_string(),
-            });
+            record_validated_match(data, i, "CramFS", "Big Endian", true, &mut seen_slabs, &mut matches);
         }
-        
+
         // Check 16-bit magics (JFFS2)
         let magic16 = u16::from_le_bytes([data[i], data[i+1]]);
-        
+
         if magic16 == JFFS2_MAGIC_BITMASK {
-            matches.push(FilesystemMatch {
-                offset: i,
-                fs_type: "JFFS2".to_string(),
-                endian: "Little Endian".to_string(),
-            });
+            record_validated_match(data, i, "JFFS2", "Little Endian", false, &mut seen_slabs, &mut matches);
         } else if magic16 == JFFS2_MAGIC_BITMASK_BE {
-            matches.push(FilesystemMatch {
-                offset: i,
-                fs_type: "JFFS2".to_string(),
-                endian: "Big Endian".to_string(),
-            });
+            record_validated_match(data, i, "JFFS2", "Big Endian", true, &mut seen_slabs, &mut matches);
         }
     }
-    
+
     matches
 }
 
@@ -129,10 +494,12 @@ fn worker_thread(
     start_key: u32,
     end_key: u32,
     results: Arc<Mutex<Vec<(Vec<u8>, FilesystemMatch)>>>,
+    candidates: Arc<Mutex<Vec<ScoredCandidate>>>,
     thread_id: usize,
     key_length: usize,
 ) {
     let mut local_results = Vec::new();
+    let mut local_candidates = Vec::new();
     let total_keys = end_key - start_key;
     
     for (count, key_val) in (start_key..end_key).enumerate() {
@@ -161,14 +528,27 @@ fn worker_thread(
             for fs_match in matches {
                 local_results.push((key_bytes.clone(), fs_match));
             }
+        } else {
+            // No exact magic, but the key might still be "almost right" --
+            // score it so an analyst can triage close misses. Sampled, not
+            // a full sliding scan: this runs once per candidate key.
+            let (min_entropy, chi2) = score_buffer_sampled(&decrypted_data);
+            record_candidate(
+                &mut local_candidates,
+                ScoredCandidate { key: key_bytes, min_entropy, chi_squared: chi2 },
+            );
         }
     }
-    
+
     // Store results
     if !local_results.is_empty() {
         let mut results_lock = results.lock().unwrap();
         results_lock.extend(local_results);
     }
+    if !local_candidates.is_empty() {
+        let mut candidates_lock = candidates.lock().unwrap();
+        candidates_lock.extend(local_candidates);
+    }
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -223,29 +603,31 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     let data = Arc::new(buffer);
     let results = Arc::new(Mutex::new(Vec::new()));
-    
+    let candidates = Arc::new(Mutex::new(Vec::new()));
+
     let keys_per_thread = ((max_key_value as u64 + 1) / num_threads as u64) as u32;
     let mut handles = vec![];
-    
+
     let start_time = std::time::Instant::now();
-    
+
     for i in 0..num_threads {
         let data_clone = Arc::clone(&data);
         let results_clone = Arc::clone(&results);
-        
+        let candidates_clone = Arc::clone(&candidates);
+
         let start_key = (i as u32) * keys_per_thread;
         let end_key = if i == num_threads - 1 {
             max_key_value
         } else {
             ((i as u32) + 1) * keys_per_thread
         };
-        
+
         println!("[*] Thread {} scanning: 0x{:08X} to 0x{:08X}", i, start_key, end_key);
-        
+
         let handle = thread::spawn(move || {
-            worker_thread(data_clone, start_key, end_key, results_clone, i, key_length);
+            worker_thread(data_clone, start_key, end_key, results_clone, candidates_clone, i, key_length);
         });
-        
+
         handles.push(handle);
     }
     
@@ -279,10 +661,30 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
     
+    let mut candidates_lock = candidates.lock().unwrap();
+    if !candidates_lock.is_empty() {
+        candidates_lock.sort_by(|a, b| a.min_entropy.partial_cmp(&b.min_entropy).unwrap());
+        candidates_lock.truncate(TOP_N_CANDIDATES);
+
+        println!("[*] No exact magic match, but these keys scored lowest entropy (worth a manual look):");
+        println!();
+        for candidate in candidates_lock.iter() {
+            print!("  [~] RC4 Key: ");
+            for byte in &candidate.key {
+                print!("{:02X}", byte);
+            }
+            println!(
+                "  min_entropy={:.3} bits/byte  chi2={:.1}",
+                candidate.min_entropy, candidate.chi_squared
+            );
+        }
+        println!();
+    }
+
     // Performance stats
     let keys_tested = max_key_value as u64 + 1;
     let keys_per_sec = keys_tested as f64 / elapsed.as_secs_f64();
     println!("[*] Performance: {:.0} keys/second", keys_per_sec);
-    
+
     Ok(())
 }